@@ -32,10 +32,47 @@ pub struct Cli {
     #[arg(long = "max-redirs", default_value = "10")]
     pub max_redirects: usize,
 
+    /// Abort the request after this many seconds.
+    #[arg(long = "timeout", value_name = "SECONDS")]
+    pub timeout: Option<u64>,
+
+    /// Retry on connection errors, timeouts, and 5xx/429 responses this many times.
+    /// Backs off exponentially between attempts, honoring a `Retry-After` response header.
+    /// Only idempotent methods (GET/HEAD/PUT/DELETE/OPTIONS) are retried unless `--retry-all` is set.
+    #[arg(long = "retry", default_value_t = 0, value_name = "N")]
+    pub retry: u32,
+
+    /// Also retry non-idempotent methods (POST/PATCH) when `--retry` is set.
+    #[arg(long = "retry-all")]
+    pub retry_all: bool,
+
     /// Print headers
     #[arg(long = "print-headers")]
     pub print_headers: bool,
 
+    /// Print the outgoing request line, headers, and a preview of the body.
+    #[arg(short = 'v', long = "verbose")]
+    pub verbose: bool,
+
+    /// Write the response body verbatim, bypassing all pretty-printing.
+    #[arg(short = 'r', long = "raw")]
+    pub raw: bool,
+
+    /// Write the response body to a file instead of stdout.
+    #[arg(short = 'o', long = "output", value_name = "PATH")]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Write the response body to a file whose name is derived from the
+    /// final URL or a `Content-Disposition` header.
+    #[arg(short = 'O', long = "remote-name")]
+    pub remote_name: bool,
+
+    /// Shorthand for the `content-type` header.
+    /// Accepts a full MIME type or one of the aliases
+    /// `json`, `form`, `xml`, `text`, `html`.
+    #[arg(short = 't', long = "type", value_name = "MIME|ALIAS")]
+    pub content_type: Option<String>,
+
     #[command(flatten)]
     pub auth_method: AuthMethod,
 
@@ -47,12 +84,6 @@ pub struct Cli {
     /// Denies the request if the body is syntactically malformed.
     #[arg(short = 'j', long = "json-body")]
     pub json: bool,
-
-    /// The body is URL encoded.
-    /// Sets the `content-type=application/x-www-form-urlencoded` header.
-    /// Multiple bodies are concatenated with a `&` between them.
-    #[arg(long = "url-encoded-body")]
-    pub url_encoded_body: bool,
 }
 
 #[derive(Args, Debug)]
@@ -84,6 +115,12 @@ pub struct BodySource {
     /// Sets the `content-type=multipart/form-data` header.
     #[arg(short = 'F', long = "form-field")]
     pub form_fields: Option<Vec<String>>,
+
+    /// Add a URL-encoded form field to the body, like `curl -d`.
+    /// Sets the `content-type=application/x-www-form-urlencoded` header.
+    /// Multiple fields are joined with `&` between them.
+    #[arg(short = 'd', long = "data", value_name = "KEY=VALUE")]
+    pub url_encoded_fields: Option<Vec<String>>,
 }
 
 #[derive(Debug, Copy, Clone, ValueEnum)]
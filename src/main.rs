@@ -8,8 +8,11 @@ use base64::prelude::*;
 use clap::Parser;
 use colored::*;
 use reqwest::{
-    blocking::{ClientBuilder, multipart::Form},
-    header::{AUTHORIZATION, CONTENT_TYPE},
+    blocking::{
+        ClientBuilder,
+        multipart::{Form, Part},
+    },
+    header::{AUTHORIZATION, CONTENT_TYPE, HeaderValue},
     redirect::Policy,
 };
 
@@ -37,6 +40,20 @@ impl BodyContent {
     }
 }
 
+/// When `validate_json` is set, parses the body as JSON5 and re-serializes it
+/// to canonical, wire-valid JSON, rejecting malformed input.
+fn prepare_body(
+    content: BodyContent,
+    validate_json: bool,
+) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !validate_json {
+        return Ok(content.to_bytes());
+    }
+    let text = content.to_string()?;
+    let value: serde_json::Value = serde_json5::from_str(&text).map_err(Error::InvalidJson)?;
+    Ok(serde_json::to_string(&value)?.into_bytes())
+}
+
 fn main() -> ExitCode {
     match run() {
         Ok(()) => ExitCode::SUCCESS,
@@ -68,6 +85,145 @@ impl std::fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+fn file_part(path: &str) -> std::result::Result<Part, Box<dyn std::error::Error>> {
+    if path == cli::STDIN {
+        let mut buffer = Vec::new();
+        std::io::stdin().read_to_end(&mut buffer)?;
+        Ok(Part::bytes(buffer)
+            .file_name("stdin")
+            .mime_str("application/octet-stream")?)
+    } else {
+        let mut file = std::fs::File::open(path)?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+        let file_name = std::path::Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path)
+            .to_string();
+        Ok(Part::bytes(buffer)
+            .file_name(file_name.clone())
+            .mime_str(guess_mime(&file_name))?)
+    }
+}
+
+fn guess_mime(file_name: &str) -> &'static str {
+    match std::path::Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+        .as_str()
+    {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+fn backoff_delay(attempt: u32) -> std::time::Duration {
+    const BASE: std::time::Duration = std::time::Duration::from_millis(200);
+    const CAP: std::time::Duration = std::time::Duration::from_secs(10);
+    BASE.saturating_mul(1 << attempt.min(16)).min(CAP)
+}
+
+fn retry_delay(response: &reqwest::blocking::Response, attempt: u32) -> std::time::Duration {
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok());
+    parse_retry_after(retry_after).unwrap_or_else(|| backoff_delay(attempt))
+}
+
+/// Parses a `Retry-After` header value, either delta-seconds or an HTTP-date.
+/// Returns `None` if the header is absent, malformed, or already in the past.
+fn parse_retry_after(value: Option<&str>) -> Option<std::time::Duration> {
+    let value = value?;
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(std::time::Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    date.duration_since(std::time::SystemTime::now()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn parse_retry_after_seconds() {
+        assert_eq!(parse_retry_after(Some("120")), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn parse_retry_after_future_http_date() {
+        let future = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(30));
+        let delay = parse_retry_after(Some(&future)).expect("future date should parse");
+        assert!(delay <= Duration::from_secs(30));
+        assert!(delay > Duration::from_secs(25));
+    }
+
+    #[test]
+    fn parse_retry_after_past_http_date_is_none() {
+        let past = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(30));
+        assert_eq!(parse_retry_after(Some(&past)), None);
+    }
+
+    #[test]
+    fn parse_retry_after_missing_header_is_none() {
+        assert_eq!(parse_retry_after(None), None);
+    }
+
+    #[test]
+    fn backoff_delay_caps_at_ten_seconds() {
+        assert_eq!(backoff_delay(0), Duration::from_millis(200));
+        assert_eq!(backoff_delay(5), Duration::from_millis(200 * 32));
+        assert_eq!(backoff_delay(6), Duration::from_secs(10));
+        assert_eq!(backoff_delay(63), Duration::from_secs(10));
+    }
+}
+
+fn resolve_content_type(value: &str) -> String {
+    match value {
+        "json" => "application/json".to_string(),
+        "form" => "application/x-www-form-urlencoded".to_string(),
+        "xml" => "application/xml".to_string(),
+        "text" => "text/plain".to_string(),
+        "html" => "text/html".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn print_request(request: &reqwest::blocking::Request) {
+    eprintln!(
+        "{} {}",
+        request.method().to_string().bold().cyan(),
+        request.url().as_str().bold()
+    );
+    for (key, value) in request.headers().iter() {
+        eprintln!(
+            "{}{}{}",
+            key.to_string().yellow().bold(),
+            "=".dimmed(),
+            value.to_str().unwrap_or("<binary>")
+        );
+    }
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        eprintln!("{}", String::from_utf8_lossy(body).dimmed());
+    }
+}
+
 fn run() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let args = cli::Cli::parse();
 
@@ -97,9 +253,13 @@ fn run() -> std::result::Result<(), Box<dyn std::error::Error>> {
         .redirect(redirect_policy)
         .build()?;
 
-    let method = args.method.into();
+    let method: reqwest::Method = args.method.into();
 
-    let mut request = client.request(method, url);
+    let mut request = client.request(method.clone(), url);
+
+    if let Some(timeout) = args.timeout {
+        request = request.timeout(std::time::Duration::from_secs(timeout));
+    }
 
     for header in &args.headers {
         let (name, value) = header
@@ -125,53 +285,126 @@ fn run() -> std::result::Result<(), Box<dyn std::error::Error>> {
         request = request.header(AUTHORIZATION, auth_value);
     }
 
-    // if args.url_encoded {
-    //     request = request.header(CONTENT_TYPE, "application/x-www-form-urlencoded");
-    // }
     if args.json {
         request = request.header(CONTENT_TYPE, "application/json");
     }
 
     request = if let Some(string) = args.body_source.string {
-        request.body(string)
+        request.body(prepare_body(BodyContent::String(string), args.json)?)
     } else if let Some(path) = args.body_source.path {
-        if *path == *cli::STDIN {
+        let content = if *path == *cli::STDIN {
             let mut buffer = Vec::new();
             std::io::stdin().read_to_end(&mut buffer)?;
-            request.body(buffer)
+            BodyContent::Binary(buffer)
         } else {
             let mut file = std::fs::File::open(path)?;
             let mut buffer = Vec::new();
             file.read_to_end(&mut buffer)?;
-            request.body(buffer)
-        }
+            BodyContent::Binary(buffer)
+        };
+        request.body(prepare_body(content, args.json)?)
     } else if let Some(form_fields) = args.body_source.form_fields {
         let mut form = Form::new();
         for field in form_fields {
             let (key, value) = field
                 .split_once('=')
                 .ok_or_else(|| Error::InvalidFormField(field.clone()))?;
-            form = form.text(key.to_string(), value.to_string());
+            form = if let Some(path) = value.strip_prefix('@') {
+                form.part(key.to_string(), file_part(path)?)
+            } else {
+                form.text(key.to_string(), value.to_string())
+            };
         }
         request.multipart(form)
+    } else if let Some(url_encoded_fields) = args.body_source.url_encoded_fields {
+        let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+        for field in &url_encoded_fields {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| Error::InvalidFormField(field.clone()))?;
+            serializer.append_pair(key, value);
+        }
+        let body = serializer.finish();
+        if args.json {
+            // `--json-body` already set `content-type=application/json` above;
+            // don't append a second `Content-Type` header on top of it, and
+            // validate the body as JSON like the other body sources.
+            request.body(prepare_body(BodyContent::String(body), args.json)?)
+        } else {
+            request
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(body)
+        }
     } else {
         request
     };
 
-    // if args.json {
-    //     let body = body.clone().to_string()?;
-    //     if let Err(err) = serde_json5::from_str::<serde_json::Value>(&body) {
-    //         return Err(Box::new(Error::InvalidJson(err)));
-    //     }
-    // }
+    let mut request = request.build()?;
 
-    // TODO:
-    // if args.body_type.url_encoded {
-    //     concatenated_body.push('&' as u8);
-    // }
+    if let Some(content_type) = &args.content_type {
+        let resolved = resolve_content_type(content_type);
+        request
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_str(&resolved)?);
+    }
+
+    if args.verbose {
+        if atty::is(atty::Stream::Stderr) {
+            colored::control::set_override(true);
+        }
+        print_request(&request);
+        colored::control::set_override(false);
+    }
+
+    let retryable_method = matches!(
+        method,
+        reqwest::Method::GET
+            | reqwest::Method::HEAD
+            | reqwest::Method::PUT
+            | reqwest::Method::DELETE
+            | reqwest::Method::OPTIONS
+    );
+    let max_retries = if retryable_method || args.retry_all {
+        args.retry
+    } else {
+        0
+    };
 
-    let request = request.build()?;
-    let mut response = client.execute(request)?;
+    let mut attempt = 0;
+    let mut current_request = request;
+    let mut response = loop {
+        let retries_remain = attempt < max_retries;
+        // Only a non-streaming body can be cloned; only bother when a retry
+        // might actually follow, so a single-shot multipart/streamed upload
+        // is never forced through try_clone().
+        let spare_request = if retries_remain {
+            Some(
+                current_request
+                    .try_clone()
+                    .ok_or("request body cannot be retried")?,
+            )
+        } else {
+            None
+        };
+        match client.execute(current_request) {
+            Ok(response)
+                if retries_remain
+                    && (response.status().is_server_error()
+                        || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS) =>
+            {
+                std::thread::sleep(retry_delay(&response, attempt));
+                attempt += 1;
+                current_request = spare_request.expect("cloned because retries_remain");
+            }
+            Ok(response) => break response,
+            Err(error) if retries_remain && (error.is_timeout() || error.is_connect()) => {
+                std::thread::sleep(backoff_delay(attempt));
+                attempt += 1;
+                current_request = spare_request.expect("cloned because retries_remain");
+            }
+            Err(error) => return Err(Box::new(error)),
+        }
+    };
     let status = response.status();
 
     if atty::is(atty::Stream::Stderr) {
@@ -207,37 +440,301 @@ fn run() -> std::result::Result<(), Box<dyn std::error::Error>> {
     }
     colored::control::set_override(false);
 
-    let response_is_json = response.headers().iter().any(|(key, value)| {
-        key == CONTENT_TYPE
-            && value
-                .to_str()
-                .is_ok_and(|value| value.contains("application/json"))
-    });
-
-    let mut bytes = Vec::new();
-    response.read_to_end(&mut bytes)?;
-    if response_is_json {
-        if atty::is(atty::Stream::Stdout) {
-            colored::control::set_override(true);
+    if let Some(path) = output_path(&args, &response) {
+        let mut file = std::fs::File::create(&path)?;
+        let total = response.content_length();
+        let show_progress = atty::is(atty::Stream::Stderr);
+        let mut received = 0u64;
+        let mut buffer = [0u8; 8192];
+        loop {
+            let read = response.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            file.write_all(&buffer[..read])?;
+            received += read as u64;
+            if show_progress {
+                print_progress(received, total);
+            }
+        }
+        if show_progress {
+            eprintln!();
         }
-        let body = String::from_utf8(bytes)?;
-        let body: serde_json::Value = serde_json5::from_str(&body)?;
-        pretty_print(&body, 0);
-        println!();
-        colored::control::set_override(false);
     } else {
-        std::io::stdout().write_all(&bytes)?;
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+
+        let mut bytes = Vec::new();
+        response.read_to_end(&mut bytes)?;
+
+        let pretty = !args.raw && atty::is(atty::Stream::Stdout);
+        if pretty && content_type.contains("application/json") {
+            colored::control::set_override(true);
+            let body = String::from_utf8(bytes)?;
+            let body: serde_json::Value = serde_json5::from_str(&body)?;
+            pretty_print(&body, 0);
+            println!();
+            colored::control::set_override(false);
+        } else if pretty && (content_type.contains("xml") || content_type.contains("html")) {
+            colored::control::set_override(true);
+            let body = String::from_utf8(bytes)?;
+            pretty_print_markup(&body);
+            println!();
+            colored::control::set_override(false);
+        } else if pretty && content_type.contains("application/x-www-form-urlencoded") {
+            colored::control::set_override(true);
+            let body = String::from_utf8(bytes)?;
+            pretty_print_form(&body);
+            colored::control::set_override(false);
+        } else {
+            std::io::stdout().write_all(&bytes)?;
+        }
     }
 
     Ok(())
 }
 
+fn output_path(
+    args: &cli::Cli,
+    response: &reqwest::blocking::Response,
+) -> Option<std::path::PathBuf> {
+    if let Some(path) = &args.output {
+        Some(path.clone())
+    } else if args.remote_name {
+        Some(std::path::PathBuf::from(derive_filename(response)))
+    } else {
+        None
+    }
+}
+
+fn derive_filename(response: &reqwest::blocking::Response) -> String {
+    if let Some(filename) = response
+        .headers()
+        .get(reqwest::header::CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_content_disposition_filename)
+        .and_then(|filename| sanitize_filename(&filename))
+    {
+        return filename;
+    }
+    response
+        .url()
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|segment| !segment.is_empty())
+        .unwrap_or("index.html")
+        .to_string()
+}
+
+/// Reduces a server-supplied filename to its final path component, the same
+/// way `file_part` does for local `@path` uploads, so a `Content-Disposition`
+/// header can't smuggle `../` traversal or an absolute path into `-O`.
+fn sanitize_filename(filename: &str) -> Option<String> {
+    std::path::Path::new(filename)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_string())
+}
+
+fn parse_content_disposition_filename(value: &str) -> Option<String> {
+    let parts: Vec<&str> = value.split(';').map(str::trim).collect();
+
+    // Prefer the RFC 5987 extended form (`filename*=charset'lang'value`),
+    // since that's what servers use to carry non-ASCII names.
+    if let Some(name) = parts
+        .iter()
+        .find_map(|part| part.strip_prefix("filename*="))
+        .and_then(parse_extended_filename)
+    {
+        return Some(name);
+    }
+
+    parts.iter().find_map(|part| {
+        part.strip_prefix("filename=")
+            .map(|name| name.trim_matches('"').to_string())
+    })
+}
+
+/// Parses an RFC 5987 `ext-value` (`charset'lang'percent-encoded-value`).
+fn parse_extended_filename(value: &str) -> Option<String> {
+    let mut segments = value.splitn(3, '\'');
+    let _charset = segments.next()?;
+    let _lang = segments.next()?;
+    let encoded = segments.next()?;
+    Some(percent_decode(encoded))
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            // Decode from raw bytes, not a `&str` slice: the two bytes after
+            // `%` aren't guaranteed to land on a char boundary when they're
+            // not actually hex digits (e.g. a literal `%` before a
+            // multi-byte UTF-8 character), and slicing would panic.
+            if let (Some(high), Some(low)) = (hex_digit(bytes[i + 1]), hex_digit(bytes[i + 2])) {
+                decoded.push(high * 16 + low);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn print_progress(received: u64, total: Option<u64>) {
+    match total {
+        Some(total) if total > 0 => {
+            let width = 30usize;
+            let ratio = (received as f64 / total as f64).clamp(0.0, 1.0);
+            let filled = (ratio * width as f64).round() as usize;
+            eprint!(
+                "\r{}{}{}{} {:>3}% ({}/{})",
+                "[".dimmed(),
+                "=".repeat(filled).green(),
+                " ".repeat(width - filled),
+                "]".dimmed(),
+                (ratio * 100.0) as u32,
+                human_bytes(received),
+                human_bytes(total),
+            );
+        }
+        _ => {
+            eprint!("\r{} {}", "received".dimmed(), human_bytes(received));
+        }
+    }
+    let _ = std::io::stderr().flush();
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes}{}", UNITS[unit])
+    } else {
+        format!("{value:.1}{}", UNITS[unit])
+    }
+}
+
 fn print_indent(depth: usize) {
     for _ in 0..depth {
         print!("  ");
     }
 }
 
+/// HTML void elements: they never have a closing tag, so they must not
+/// push `depth` the way a normal container element does.
+const HTML_VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param",
+    "source", "track", "wbr",
+];
+
+fn tag_name(tag: &str) -> &str {
+    let inner = tag
+        .trim_start_matches("</")
+        .trim_start_matches('<')
+        .trim_end_matches("/>")
+        .trim_end_matches('>');
+    inner.split_whitespace().next().unwrap_or(inner)
+}
+
+fn is_void_element(tag: &str) -> bool {
+    HTML_VOID_ELEMENTS.contains(&tag_name(tag).to_lowercase().as_str())
+}
+
+fn pretty_print_markup(input: &str) {
+    let mut depth = 0usize;
+    let mut cursor = 0usize;
+    while cursor < input.len() {
+        if input[cursor..].starts_with('<') {
+            let end = input[cursor..]
+                .find('>')
+                .map(|index| cursor + index + 1)
+                .unwrap_or(input.len());
+            let tag = &input[cursor..end];
+            let is_closing = tag.starts_with("</");
+            let is_self_closing = tag.ends_with("/>")
+                || tag.starts_with("<?")
+                || tag.starts_with("<!")
+                || is_void_element(tag);
+            if is_closing {
+                depth = depth.saturating_sub(1);
+            }
+            print_indent(depth);
+            print_tag(tag);
+            println!();
+            if !is_closing && !is_self_closing {
+                depth += 1;
+            }
+            cursor = end;
+        } else {
+            let end = input[cursor..]
+                .find('<')
+                .map(|index| cursor + index)
+                .unwrap_or(input.len());
+            let text = input[cursor..end].trim();
+            if !text.is_empty() {
+                print_indent(depth);
+                println!("{text}");
+            }
+            cursor = end;
+        }
+    }
+}
+
+fn print_tag(tag: &str) {
+    let is_closing = tag.starts_with("</");
+    let is_self_closing = tag.ends_with("/>");
+    let inner = tag
+        .trim_start_matches("</")
+        .trim_start_matches('<')
+        .trim_end_matches("/>")
+        .trim_end_matches('>');
+    let name = tag_name(tag);
+    let attributes = inner.strip_prefix(name).unwrap_or("");
+
+    print!("{}", "<".bright_black());
+    if is_closing {
+        print!("{}", "/".bright_black());
+    }
+    print!("{}", name.bold().bright_yellow());
+    if !attributes.trim().is_empty() {
+        print!(" {}", attributes.trim().cyan());
+    }
+    if is_self_closing {
+        print!("{}", " /".bright_black());
+    }
+    print!("{}", ">".bright_black());
+}
+
+fn pretty_print_form(body: &str) {
+    for (key, value) in url::form_urlencoded::parse(body.as_bytes()) {
+        println!("{}{}{}", key.bold().bright_yellow(), "=".dimmed(), value);
+    }
+}
+
 fn pretty_print(value: &serde_json::Value, depth: usize) {
     match value {
         serde_json::Value::Null => print!("{}", "null".bright_magenta()),